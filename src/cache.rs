@@ -0,0 +1,169 @@
+use crate::dns::{DNSRecord, RecordType};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Backing store for resolved records, keyed by (name, type, class). Kept
+/// behind a trait so the resolver can run against a plain in-memory cache or
+/// a persistent one without caring which.
+pub trait CacheStore {
+    /// Live records for `(name, type_, class)`, if any haven't expired yet.
+    /// The returned records' `ttl` is the *remaining* time, not the original.
+    fn get(&mut self, name: &str, type_: RecordType, class: u16) -> Option<Vec<DNSRecord>>;
+
+    /// Store `record`, computing its expiry as `now + record.ttl`.
+    fn insert(&mut self, record: &DNSRecord, class: u16);
+}
+
+struct CacheEntry {
+    record: DNSRecord,
+    expires_at: Instant
+}
+
+/// Default `CacheStore`: everything lives in a `HashMap` and is lost on
+/// restart.
+#[derive(Default)]
+pub struct MemoryCache {
+    entries: HashMap<(String, RecordType, u16), Vec<CacheEntry>>
+}
+
+impl MemoryCache {
+    pub fn new() -> MemoryCache {
+        MemoryCache { entries: HashMap::new() }
+    }
+}
+
+impl CacheStore for MemoryCache {
+    fn get(&mut self, name: &str, type_: RecordType, class: u16) -> Option<Vec<DNSRecord>> {
+        let key = (name.to_string(), type_, class);
+        let now = Instant::now();
+
+        let entries = self.entries.get_mut(&key)?;
+        entries.retain(|entry| entry.expires_at > now);
+        if entries.is_empty() {
+            self.entries.remove(&key);
+            return None;
+        }
+
+        Some(entries.iter().map(|entry| {
+            let mut record = entry.record.clone();
+            record.ttl = (entry.expires_at - now).as_secs() as u32;
+            record
+        }).collect())
+    }
+
+    fn insert(&mut self, record: &DNSRecord, class: u16) {
+        let key = (record.name.clone(), record.type_, class);
+        let expires_at = Instant::now() + Duration::from_secs(record.ttl as u64);
+        self.entries.entry(key).or_default().push(CacheEntry { record: record.clone(), expires_at });
+    }
+}
+
+// A `CacheStore` backed by SQLite (name/type/ttl/data rows), for a resolver
+// that keeps its cache across restarts instead of starting cold every time.
+// Lives behind a feature flag since it pulls in `rusqlite`, which most
+// embedders of this crate won't want. This binary's own `main` doesn't wire
+// it up -- it's here for embedders of this crate that do want a persistent
+// cache, so it's expected to look unused when this crate builds on its own.
+#[cfg(feature = "sqlite-cache")]
+pub mod sqlite {
+    use super::CacheStore;
+    use crate::dns::{DNSRecord, RData, RecordType};
+    use rusqlite::{params, Connection};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[allow(dead_code)]
+    pub struct SqliteCache {
+        conn: Connection
+    }
+
+    #[allow(dead_code)]
+    impl SqliteCache {
+        pub fn open(path: &str) -> rusqlite::Result<SqliteCache> {
+            let conn = Connection::open(path)?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS cache (
+                    name TEXT NOT NULL,
+                    type_ INTEGER NOT NULL,
+                    class INTEGER NOT NULL,
+                    ttl INTEGER NOT NULL,
+                    expires_at INTEGER NOT NULL,
+                    data BLOB NOT NULL
+                )",
+                []
+            )?;
+            Ok(SqliteCache { conn })
+        }
+
+        fn now() -> i64 {
+            SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+        }
+    }
+
+    impl CacheStore for SqliteCache {
+        fn get(&mut self, name: &str, type_: RecordType, class: u16) -> Option<Vec<DNSRecord>> {
+            let now = Self::now();
+            self.conn.execute("DELETE FROM cache WHERE expires_at <= ?1", params![now]).ok()?;
+
+            let type_num: u16 = type_.into();
+            let mut stmt = self.conn.prepare(
+                "SELECT expires_at, data FROM cache WHERE name = ?1 AND type_ = ?2 AND class = ?3"
+            ).ok()?;
+
+            let rows = stmt.query_map(params![name, type_num, class], |row| {
+                let expires_at: i64 = row.get(0)?;
+                let data: Vec<u8> = row.get(1)?;
+                Ok((expires_at, data))
+            }).ok()?;
+
+            let records: Vec<DNSRecord> = rows.filter_map(|row| row.ok())
+                .filter_map(|(expires_at, data)| {
+                    let data = RData::from_plain_bytes(type_, &data).ok()?;
+                    Some(DNSRecord {
+                        name: name.to_string(),
+                        type_,
+                        class,
+                        ttl: (expires_at - now).max(0) as u32,
+                        data
+                    })
+                })
+                .collect();
+
+            if records.is_empty() { None } else { Some(records) }
+        }
+
+        fn insert(&mut self, record: &DNSRecord, class: u16) {
+            let type_num: u16 = record.type_.into();
+            let expires_at = Self::now() + record.ttl as i64;
+            let _ = self.conn.execute(
+                "INSERT INTO cache (name, type_, class, ttl, expires_at, data) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![record.name, type_num, class, record.ttl, expires_at, record.data.to_bytes()]
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::net::Ipv4Addr;
+
+        #[test]
+        fn round_trips_an_a_record() {
+            let mut cache = SqliteCache::open(":memory:").unwrap();
+            let record = DNSRecord {
+                name: "example.com".to_string(),
+                type_: RecordType::A,
+                class: 1,
+                ttl: 300,
+                data: RData::A(Ipv4Addr::new(93, 184, 216, 34))
+            };
+
+            cache.insert(&record, 1);
+
+            let got = cache.get("example.com", RecordType::A, 1).unwrap();
+            match got.first().map(|record| &record.data) {
+                Some(RData::A(ip)) => assert_eq!(*ip, Ipv4Addr::new(93, 184, 216, 34)),
+                other => panic!("expected a stored A record, got {:?}", other)
+            }
+        }
+    }
+}
@@ -0,0 +1,596 @@
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+pub const CLASS_IN: u16 = 1;
+
+// Caps from the DNS spec: a label is at most 63 bytes and a full name at
+// most 255, and bounding both (rather than trusting `data_len`/length
+// prefixes from the wire) keeps a malformed or hostile packet from making us
+// allocate without limit.
+const MAX_LABEL_LEN: usize = 63;
+const MAX_NAME_LEN: usize = 255;
+
+#[derive(Debug)]
+pub enum DnsParseError {
+    /// The buffer ran out before a fixed-size field or a length-prefixed
+    /// field could be fully read.
+    Truncated,
+    /// A label's bytes were not valid UTF-8.
+    InvalidLabel,
+    /// A compression pointer pointed forward, at itself, or at an offset
+    /// that was not already a fully-decoded name -- any of which would mean
+    /// following it loops or reads garbage.
+    InvalidCompressionPointer,
+    /// A label was longer than 63 bytes.
+    LabelTooLong,
+    /// A name's total encoded length exceeded 255 bytes.
+    NameTooLong
+}
+
+impl fmt::Display for DnsParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsParseError::Truncated => write!(f, "packet ended before an expected field"),
+            DnsParseError::InvalidLabel => write!(f, "label is not valid UTF-8"),
+            DnsParseError::InvalidCompressionPointer => write!(f, "compression pointer is forward-pointing or unresolved"),
+            DnsParseError::LabelTooLong => write!(f, "label exceeds {} bytes", MAX_LABEL_LEN),
+            DnsParseError::NameTooLong => write!(f, "name exceeds {} bytes", MAX_NAME_LEN)
+        }
+    }
+}
+
+impl std::error::Error for DnsParseError {}
+
+fn pop_u8(buffer: &mut VecDeque<u8>) -> Result<u8, DnsParseError> {
+    buffer.pop_front().ok_or(DnsParseError::Truncated)
+}
+
+fn pop_u16(buffer: &mut VecDeque<u8>) -> Result<u16, DnsParseError> {
+    Ok(u16::from_be_bytes([pop_u8(buffer)?, pop_u8(buffer)?]))
+}
+
+fn pop_u32(buffer: &mut VecDeque<u8>) -> Result<u32, DnsParseError> {
+    Ok(u32::from_be_bytes([pop_u8(buffer)?, pop_u8(buffer)?, pop_u8(buffer)?, pop_u8(buffer)?]))
+}
+
+fn drain_bytes(buffer: &mut VecDeque<u8>, len: usize) -> Result<Vec<u8>, DnsParseError> {
+    if buffer.len() < len {
+        return Err(DnsParseError::Truncated);
+    }
+    Ok(buffer.drain(0..len).collect())
+}
+
+// When the first 2 bits are 1, the domain name is compressed.
+// Check by ANDing with 0b11000000
+fn is_compressed(byte: usize) -> bool {
+    byte & 0b11000000 == 0b11000000
+}
+
+
+#[derive(Debug, Clone)]
+pub struct DNSHeader {
+    pub id: u16,
+    pub flags: u16,
+    pub num_questions: u16,
+    pub num_answers: u16,
+    pub num_authorities: u16,
+    pub num_additionals: u16
+}
+
+impl DNSHeader {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.id.to_be_bytes());
+        bytes.extend_from_slice(&self.flags.to_be_bytes());
+        bytes.extend_from_slice(&self.num_questions.to_be_bytes());
+        bytes.extend_from_slice(&self.num_answers.to_be_bytes());
+        bytes.extend_from_slice(&self.num_authorities.to_be_bytes());
+        bytes.extend_from_slice(&self.num_additionals.to_be_bytes());
+        bytes
+    }
+
+    fn from_buffer(buffer: &mut VecDeque<u8>, idx: &mut u16) -> Result<DNSHeader, DnsParseError> {
+        let header_vals: Vec<u16> = drain_bytes(buffer, 12)?.chunks_exact(2)
+            .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
+            .collect();
+        *idx += 12;
+
+        Ok(DNSHeader {
+            id: header_vals[0],
+            flags: header_vals[1],
+            num_questions: header_vals[2],
+            num_answers: header_vals[3],
+            num_authorities: header_vals[4],
+            num_additionals: header_vals[5]
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DNSQuestion {
+    pub name: String,
+    pub type_: u16,
+    pub class: u16
+}
+
+impl DNSQuestion {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&encode_dns_name(&self.name));
+        bytes.extend_from_slice(&self.type_.to_be_bytes());
+        bytes.extend_from_slice(&self.class.to_be_bytes());
+        bytes
+    }
+
+    fn from_buffer(buffer: &mut VecDeque<u8>, idx: &mut u16, decoded_names: &mut HashMap<u16, String>) -> Result<DNSQuestion, DnsParseError> {
+        let name = decode_name(buffer, idx, decoded_names)?;
+
+        let type_ = pop_u16(buffer)?;
+        let class = pop_u16(buffer)?;
+        *idx += 4;
+
+        Ok(DNSQuestion {
+            name,
+            type_,
+            class
+        })
+    }
+
+}
+
+// Named after the DNS record types they represent, not Rust naming
+// conventions -- AAAA/CNAME/SOA/TXT are how every DNS RFC and resolver
+// spells them.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordType {
+    A,
+    NS,
+    CNAME,
+    SOA,
+    MX,
+    TXT,
+    AAAA,
+    Other(u16)
+}
+
+impl From<u16> for RecordType {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => RecordType::A,
+            2 => RecordType::NS,
+            5 => RecordType::CNAME,
+            6 => RecordType::SOA,
+            15 => RecordType::MX,
+            16 => RecordType::TXT,
+            28 => RecordType::AAAA,
+            other => RecordType::Other(other)
+        }
+    }
+}
+
+impl From<RecordType> for u16 {
+    fn from(value: RecordType) -> Self {
+        match value {
+            RecordType::A => 1,
+            RecordType::NS => 2,
+            RecordType::CNAME => 5,
+            RecordType::SOA => 6,
+            RecordType::MX => 15,
+            RecordType::TXT => 16,
+            RecordType::AAAA => 28,
+            RecordType::Other(raw) => raw
+        }
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone)]
+pub enum RData {
+    A(Ipv4Addr),
+    AAAA(Ipv6Addr),
+    NS(String),
+    CNAME(String),
+    MX { preference: u16, exchange: String },
+    TXT(Vec<String>),
+    SOA {
+        mname: String,
+        rname: String,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32
+    },
+    Other(Vec<u8>)
+}
+
+impl RData {
+    // `decoded_names` carries compression offsets for the *whole* message, so
+    // name-bearing RDATA (NS/CNAME/MX/SOA) can resolve pointers that point
+    // back outside this record's own data, not just within it.
+    fn from_buffer(buffer: &mut VecDeque<u8>, idx: &mut u16, decoded_names: &mut HashMap<u16, String>, type_: RecordType, data_len: u16) -> Result<RData, DnsParseError> {
+        let end_idx = idx.saturating_add(data_len);
+
+        Ok(match type_ {
+            RecordType::A => {
+                let octets = drain_bytes(buffer, 4)?;
+                *idx += 4;
+                RData::A(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+            }
+            RecordType::AAAA => {
+                let octets = drain_bytes(buffer, 16)?;
+                *idx += 16;
+                let segments: Vec<u16> = octets.chunks_exact(2)
+                    .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
+                    .collect();
+                RData::AAAA(Ipv6Addr::new(
+                    segments[0], segments[1], segments[2], segments[3],
+                    segments[4], segments[5], segments[6], segments[7]
+                ))
+            }
+            RecordType::NS => RData::NS(decode_name(buffer, idx, decoded_names)?),
+            RecordType::CNAME => RData::CNAME(decode_name(buffer, idx, decoded_names)?),
+            RecordType::MX => {
+                let preference = pop_u16(buffer)?;
+                *idx += 2;
+                let exchange = decode_name(buffer, idx, decoded_names)?;
+                RData::MX { preference, exchange }
+            }
+            RecordType::TXT => {
+                let mut strings = Vec::new();
+                while *idx < end_idx {
+                    let len = pop_u8(buffer)? as usize;
+                    *idx += 1;
+                    let part = drain_bytes(buffer, len)?;
+                    *idx += len as u16;
+                    strings.push(String::from_utf8(part).map_err(|_| DnsParseError::InvalidLabel)?);
+                }
+                RData::TXT(strings)
+            }
+            RecordType::SOA => {
+                let mname = decode_name(buffer, idx, decoded_names)?;
+                let rname = decode_name(buffer, idx, decoded_names)?;
+                let fields: Vec<u32> = drain_bytes(buffer, 20)?.chunks_exact(4)
+                    .map(|chunk| u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+                    .collect();
+                *idx += 20;
+                RData::SOA {
+                    mname,
+                    rname,
+                    serial: fields[0],
+                    refresh: fields[1],
+                    retry: fields[2],
+                    expire: fields[3],
+                    minimum: fields[4]
+                }
+            }
+            RecordType::Other(_) => {
+                let bytes = drain_bytes(buffer, data_len as usize)?;
+                *idx += data_len;
+                RData::Other(bytes)
+            }
+        })
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            RData::A(ip) => ip.octets().to_vec(),
+            RData::AAAA(ip) => ip.octets().to_vec(),
+            RData::NS(name) => encode_dns_name(name),
+            RData::CNAME(name) => encode_dns_name(name),
+            RData::MX { preference, exchange } => {
+                let mut bytes = preference.to_be_bytes().to_vec();
+                bytes.extend_from_slice(&encode_dns_name(exchange));
+                bytes
+            }
+            RData::TXT(strings) => {
+                let mut bytes = Vec::new();
+                for part in strings {
+                    bytes.push(part.len() as u8);
+                    bytes.extend_from_slice(part.as_bytes());
+                }
+                bytes
+            }
+            RData::SOA { mname, rname, serial, refresh, retry, expire, minimum } => {
+                let mut bytes = encode_dns_name(mname);
+                bytes.extend_from_slice(&encode_dns_name(rname));
+                bytes.extend_from_slice(&serial.to_be_bytes());
+                bytes.extend_from_slice(&refresh.to_be_bytes());
+                bytes.extend_from_slice(&retry.to_be_bytes());
+                bytes.extend_from_slice(&expire.to_be_bytes());
+                bytes.extend_from_slice(&minimum.to_be_bytes());
+                bytes
+            }
+            RData::Other(bytes) => bytes.clone()
+        }
+    }
+
+    /// Reconstructs the `RData` that `to_bytes()` produced, given the record's
+    /// `type_`. For a store like `SqliteCache` that persists only the raw
+    /// RDATA bytes, with no surrounding packet to carry compression pointers
+    /// -- `to_bytes()` never emits any, so parsing against an empty
+    /// `decoded_names` map is safe. This binary's own `main` doesn't wire up
+    /// `SqliteCache`, so this looks unused when the crate builds on its own
+    /// -- it's here for that feature's only caller.
+    #[cfg(feature = "sqlite-cache")]
+    #[allow(dead_code)]
+    pub(crate) fn from_plain_bytes(type_: RecordType, bytes: &[u8]) -> Result<RData, DnsParseError> {
+        let mut buffer = VecDeque::from(bytes.to_vec());
+        let mut idx = 0u16;
+        let mut decoded_names = HashMap::new();
+        RData::from_buffer(&mut buffer, &mut idx, &mut decoded_names, type_, bytes.len() as u16)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DNSRecord {
+    pub name: String,
+    pub type_: RecordType,
+    pub class: u16,
+    pub ttl: u32,
+    pub data: RData
+}
+
+impl DNSRecord {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&encode_dns_name(&self.name));
+        bytes.extend_from_slice(&u16::from(self.type_).to_be_bytes());
+        bytes.extend_from_slice(&self.class.to_be_bytes());
+        bytes.extend_from_slice(&self.ttl.to_be_bytes());
+
+        let data = self.data.to_bytes();
+        bytes.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&data);
+        bytes
+    }
+
+    fn from_buffer(buffer: &mut VecDeque<u8>, idx: &mut u16, decoded_names: &mut HashMap<u16, String>) -> Result<DNSRecord, DnsParseError> {
+        let name = decode_name(buffer, idx, decoded_names)?;
+
+        let type_ = RecordType::from(pop_u16(buffer)?);
+        let class = pop_u16(buffer)?;
+        let ttl = pop_u32(buffer)?;
+        let data_len = pop_u16(buffer)?;
+        *idx += 10;
+
+        let data = RData::from_buffer(buffer, idx, decoded_names, type_, data_len)?;
+
+        Ok(DNSRecord {
+            name,
+            type_,
+            class,
+            ttl,
+            data
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct DNSPacket {
+    pub header: DNSHeader,
+    pub questions: Vec<DNSQuestion>,
+    pub answers: Vec<DNSRecord>,
+    pub authorities: Vec<DNSRecord>,
+    pub additionals: Vec<DNSRecord>
+}
+
+impl DNSPacket {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.header.to_bytes());
+        for question in &self.questions {
+            bytes.extend_from_slice(&question.to_bytes());
+        }
+        for record in self.answers.iter().chain(&self.authorities).chain(&self.additionals) {
+            bytes.extend_from_slice(&record.to_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_buffer(buffer: Vec<u8>) -> Result<DNSPacket, DnsParseError> {
+        let mut response = VecDeque::from(buffer);
+        let mut idx = 0;
+        let mut decoded_names: HashMap<u16, String> = HashMap::new();
+
+        let header = DNSHeader::from_buffer(&mut response, &mut idx)?;
+
+        let mut questions: Vec<DNSQuestion> = Vec::new();
+        for _ in 0..header.num_questions {
+            questions.push(DNSQuestion::from_buffer(&mut response, &mut idx, &mut decoded_names)?);
+        }
+
+        let mut answers: Vec<DNSRecord> = Vec::new();
+        for _ in 0..header.num_answers {
+            answers.push(DNSRecord::from_buffer(&mut response, &mut idx, &mut decoded_names)?);
+        }
+
+        let mut authorities: Vec<DNSRecord> = Vec::new();
+        for _ in 0..header.num_authorities {
+            authorities.push(DNSRecord::from_buffer(&mut response, &mut idx, &mut decoded_names)?);
+        }
+
+        let mut additionals: Vec<DNSRecord> = Vec::new();
+        for _ in 0..header.num_additionals {
+            additionals.push(DNSRecord::from_buffer(&mut response, &mut idx, &mut decoded_names)?);
+        }
+
+        Ok(DNSPacket { header, questions, answers, authorities, additionals })
+    }
+}
+
+fn decode_name(buffer: &mut VecDeque<u8>, idx: &mut u16, decoded_names: &mut HashMap<u16, String>) -> Result<String, DnsParseError> {
+    let name_idx = *idx;
+
+    let mut len = pop_u8(buffer)? as usize;
+    *idx += 1;
+
+    if is_compressed(len) {
+        let pointer = u16::from_be_bytes([(len & 0b0011_1111) as u8, pop_u8(buffer)?]);
+        *idx += 1;
+
+        // A pointer must point strictly backward at a name we've already
+        // fully decoded. Forward pointers, self-references, and anything
+        // still mid-parse (including a cycle of pointers) simply won't be in
+        // `decoded_names` yet, so this one check catches all three.
+        if pointer >= name_idx {
+            return Err(DnsParseError::InvalidCompressionPointer);
+        }
+        let name = decoded_names.get(&pointer).cloned().ok_or(DnsParseError::InvalidCompressionPointer)?;
+        decoded_names.insert(name_idx, name.clone());
+        return Ok(name);
+    }
+
+    let mut name_parts = Vec::new();
+    let mut label_offsets = Vec::new();
+    let mut total_len = 0usize;
+    let mut label_offset = name_idx;
+
+    loop {
+        if len > MAX_LABEL_LEN {
+            return Err(DnsParseError::LabelTooLong);
+        }
+
+        label_offsets.push(label_offset);
+
+        let part = drain_bytes(buffer, len)?;
+        *idx += len as u16;
+
+        total_len += len + 1; // + the length-prefix byte this label started with
+        if total_len > MAX_NAME_LEN {
+            return Err(DnsParseError::NameTooLong);
+        }
+
+        name_parts.push(String::from_utf8(part).map_err(|_| DnsParseError::InvalidLabel)?);
+
+        label_offset = *idx;
+        len = pop_u8(buffer)? as usize;
+        *idx += 1;
+
+        // A name may end in a pointer to an earlier name's suffix instead of
+        // a zero-length label (e.g. "b" then a pointer to "gtld-servers.net").
+        if is_compressed(len) {
+            let pointer = u16::from_be_bytes([(len & 0b0011_1111) as u8, pop_u8(buffer)?]);
+            *idx += 1;
+            if pointer >= label_offset {
+                return Err(DnsParseError::InvalidCompressionPointer);
+            }
+            let suffix = decoded_names.get(&pointer).cloned().ok_or(DnsParseError::InvalidCompressionPointer)?;
+            total_len += suffix.len() + 1;
+            if total_len > MAX_NAME_LEN {
+                return Err(DnsParseError::NameTooLong);
+            }
+            name_parts.push(suffix);
+            len = 0;
+        }
+        if len == 0 {
+            // A later name can point at any label within this one, not just
+            // its start (e.g. a second NS name pointing straight at the
+            // "gtld-servers.net" suffix of an earlier, longer name), so every
+            // label offset we passed through needs its own suffix recorded.
+            for (i, &offset) in label_offsets.iter().enumerate() {
+                decoded_names.insert(offset, name_parts[i..].join("."));
+            }
+            return Ok(name_parts.join("."));
+        }
+    }
+}
+
+fn encode_dns_name(name: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let parts: Vec<&str> = name.split('.').collect();
+    for part in parts {
+        bytes.push(part.len() as u8);
+        bytes.extend_from_slice(part.as_bytes());
+    }
+    bytes.push(0);
+    bytes
+}
+
+// Hardcode class to IN.
+pub fn build_query(domain_name: String, record_type: RecordType) -> Vec<u8> {
+    let id = rand::thread_rng().gen_range(0..65535);
+    let recursion_desired = 1 << 8;
+    let header = DNSHeader {
+        id,
+        flags: recursion_desired,
+        num_questions: 1,
+        num_answers: 0,
+        num_authorities: 0,
+        num_additionals: 0
+    };
+    let question = DNSQuestion {
+        name: domain_name,
+        type_: record_type.into(),
+        class: CLASS_IN
+    };
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&header.to_bytes());
+    bytes.extend_from_slice(&question.to_bytes());
+    bytes
+}
+
+/// Same as `build_query`, but with the recursion-desired flag cleared so a
+/// server being queried iteratively does not try to resolve it for us.
+pub fn build_iterative_query(domain_name: String, record_type: RecordType) -> Vec<u8> {
+    let mut query = build_query(domain_name, record_type);
+    query[2] &= !(1 << 0); // clear the RD bit in the flags byte
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(buffer: Vec<u8>, idx: u16) -> Result<String, DnsParseError> {
+        let mut buffer = VecDeque::from(buffer);
+        let mut decoded_names = HashMap::new();
+        let mut idx = idx;
+        decode_name(&mut buffer, &mut idx, &mut decoded_names)
+    }
+
+    #[test]
+    fn rejects_a_forward_pointer() {
+        // A pointer at offset 0 to offset 2, which hasn't been decoded yet.
+        let buffer = vec![0xC0, 0x02];
+        assert!(matches!(decode(buffer, 0), Err(DnsParseError::InvalidCompressionPointer)));
+    }
+
+    #[test]
+    fn rejects_a_self_pointer() {
+        // A pointer at offset 0 to offset 0 -- itself.
+        let buffer = vec![0xC0, 0x00];
+        assert!(matches!(decode(buffer, 0), Err(DnsParseError::InvalidCompressionPointer)));
+    }
+
+    #[test]
+    fn rejects_a_truncated_buffer() {
+        // A label claiming 5 bytes but only 2 are present.
+        let buffer = vec![5, b'a', b'b'];
+        assert!(matches!(decode(buffer, 0), Err(DnsParseError::Truncated)));
+    }
+
+    #[test]
+    fn rejects_a_label_over_63_bytes() {
+        let mut buffer = vec![64];
+        buffer.extend(std::iter::repeat_n(b'a', 64));
+        assert!(matches!(decode(buffer, 0), Err(DnsParseError::LabelTooLong)));
+    }
+
+    #[test]
+    fn rejects_a_name_over_255_bytes() {
+        // Four 63-byte labels: each contributes 64 to the running total, so
+        // the fourth pushes it to 256, over the 255-byte cap, with no
+        // terminating zero-length label needed to trigger it.
+        let mut buffer = Vec::new();
+        for _ in 0..4 {
+            buffer.push(63);
+            buffer.extend(std::iter::repeat_n(b'a', 63));
+        }
+        assert!(matches!(decode(buffer, 0), Err(DnsParseError::NameTooLong)));
+    }
+}
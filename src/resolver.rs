@@ -0,0 +1,138 @@
+use crate::cache::CacheStore;
+use crate::dns::{build_iterative_query, DNSPacket, DNSRecord, RData, RecordType, CLASS_IN};
+use crate::zone::ZoneStore;
+use std::collections::HashSet;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+
+// a.root-servers.net, used as the starting point for the iterative walk.
+const ROOT_SERVER: Ipv4Addr = Ipv4Addr::new(198, 41, 0, 4);
+
+// Generous enough for any real delegation chain; stops runaway loops between
+// misconfigured or adversarial servers from hanging the resolver forever.
+const MAX_STEPS: usize = 30;
+
+/// Resolve `name`/`record_type` by walking the DNS hierarchy ourselves,
+/// starting from a root server, rather than asking a recursive resolver to
+/// do it for us. Checks `zone` and then `cache` before touching the network,
+/// and populates the cache with everything seen along the way.
+pub fn resolve(name: &str, record_type: RecordType, cache: &mut dyn CacheStore, zone: &dyn ZoneStore) -> io::Result<Vec<DNSRecord>> {
+    let mut budget = MAX_STEPS;
+    resolve_with_budget(name, record_type, cache, zone, &mut budget)
+}
+
+// Does the actual walk, decrementing a `budget` shared across every nested
+// `resolve` call this one makes (e.g. to look up missing glue). Two domains
+// that delegate to each other's nameservers with no glue would otherwise
+// bounce between separate top-level `resolve` calls forever, each starting
+// its own fresh step count; sharing the budget caps the total depth instead
+// of just each call's own loop.
+fn resolve_with_budget(name: &str, record_type: RecordType, cache: &mut dyn CacheStore, zone: &dyn ZoneStore, budget: &mut usize) -> io::Result<Vec<DNSRecord>> {
+    if let Some(records) = zone.lookup(name, record_type) {
+        return Ok(records);
+    }
+
+    if let Some(cached) = cache.get(name, record_type, CLASS_IN) {
+        return Ok(cached);
+    }
+
+    let mut name = name.to_string();
+    let mut nameserver = IpAddr::V4(ROOT_SERVER);
+    let mut visited: HashSet<IpAddr> = HashSet::new();
+
+    loop {
+        if *budget == 0 {
+            return Err(io::Error::other("too many delegations while resolving"));
+        }
+        *budget -= 1;
+
+        if !visited.insert(nameserver) {
+            return Err(io::Error::other("delegation loop detected"));
+        }
+
+        let packet = query(&name, record_type, nameserver)?;
+        cache_packet(&packet, cache);
+
+        if let Some(cname) = find_cname(&packet.answers) {
+            if record_type != RecordType::CNAME {
+                if let Some(cached) = cache.get(&cname, record_type, CLASS_IN) {
+                    return Ok(cached);
+                }
+                name = cname;
+                nameserver = IpAddr::V4(ROOT_SERVER);
+                visited.clear();
+                continue;
+            }
+        }
+
+        if !packet.answers.is_empty() {
+            return Ok(packet.answers);
+        }
+
+        match next_nameserver(&packet, cache, zone, budget)? {
+            Some(ip) => nameserver = ip,
+            None => return Ok(Vec::new())
+        }
+    }
+}
+
+fn cache_packet(packet: &DNSPacket, cache: &mut dyn CacheStore) {
+    for record in packet.answers.iter().chain(&packet.authorities).chain(&packet.additionals) {
+        cache.insert(record, CLASS_IN);
+    }
+}
+
+fn query(name: &str, record_type: RecordType, nameserver: IpAddr) -> io::Result<DNSPacket> {
+    let query = build_iterative_query(name.to_string(), record_type);
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.send_to(&query, (nameserver, 53))?;
+
+    let mut buffer = [0u8; 1024];
+    let (amt, _) = socket.recv_from(&mut buffer)?;
+    DNSPacket::from_buffer(buffer[..amt].to_vec())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+}
+
+fn find_cname(answers: &[DNSRecord]) -> Option<String> {
+    answers.iter().find_map(|record| match &record.data {
+        RData::CNAME(target) => Some(target.clone()),
+        _ => None
+    })
+}
+
+// Picks an NS out of `authorities` and finds its address, preferring glue
+// already present in `additionals` over a fresh recursive lookup.
+fn next_nameserver(packet: &DNSPacket, cache: &mut dyn CacheStore, zone: &dyn ZoneStore, budget: &mut usize) -> io::Result<Option<IpAddr>> {
+    let ns_name = match packet.authorities.iter().find_map(|record| match &record.data {
+        RData::NS(name) => Some(name.clone()),
+        _ => None
+    }) {
+        Some(name) => name,
+        None => return Ok(None)
+    };
+
+    if let Some(ip) = glue_address(&packet.additionals, &ns_name) {
+        return Ok(Some(ip));
+    }
+
+    let glue_answers = resolve_with_budget(&ns_name, RecordType::A, cache, zone, budget)?;
+    match glue_answers.iter().find_map(|record| match record.data {
+        RData::A(ip) => Some(IpAddr::V4(ip)),
+        _ => None
+    }) {
+        Some(ip) => Ok(Some(ip)),
+        None => Ok(None)
+    }
+}
+
+fn glue_address(additionals: &[DNSRecord], ns_name: &str) -> Option<IpAddr> {
+    additionals.iter().find_map(|record| {
+        if record.name != ns_name {
+            return None;
+        }
+        match record.data {
+            RData::A(ip) => Some(IpAddr::V4(ip)),
+            _ => None
+        }
+    })
+}
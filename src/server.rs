@@ -0,0 +1,85 @@
+use crate::cache::{CacheStore, MemoryCache};
+use crate::dns::{DNSHeader, DNSPacket, DNSQuestion, DNSRecord, RecordType};
+use crate::resolver::resolve;
+use crate::zone::{NoopZoneStore, ZoneStore};
+use std::collections::HashMap;
+use std::io;
+use std::net::UdpSocket;
+
+const FLAG_QR_RESPONSE: u16 = 1 << 15;
+
+const RCODE_NO_ERROR: u16 = 0;
+const RCODE_FORMAT_ERROR: u16 = 1;
+const RCODE_SERVER_FAILURE: u16 = 2;
+const RCODE_NAME_ERROR: u16 = 3;
+
+/// A small authoritative zone: answers served straight from memory, keyed by
+/// the exact (name, type) a question asks for.
+pub type Zone = HashMap<(String, RecordType), Vec<DNSRecord>>;
+
+/// Serve DNS over UDP on `bind_addr`, answering from `zone` first, then
+/// `overlay`'s signed records, then falling back to the iterative resolver.
+pub fn serve(bind_addr: &str, zone: &Zone) -> io::Result<()> {
+    serve_with_overlay(bind_addr, zone, &NoopZoneStore)
+}
+
+pub fn serve_with_overlay(bind_addr: &str, zone: &Zone, overlay: &dyn ZoneStore) -> io::Result<()> {
+    let socket = UdpSocket::bind(bind_addr)?;
+    let mut cache = MemoryCache::new();
+    let mut buffer = [0u8; 512];
+
+    loop {
+        let (amt, src) = socket.recv_from(&mut buffer)?;
+
+        // A packet that fails to parse anywhere is dropped without a reply,
+        // even when its header (and so its query id) was readable -- and we
+        // keep serving everyone else.
+        let query = match DNSPacket::from_buffer(buffer[..amt].to_vec()) {
+            Ok(query) => query,
+            Err(_) => continue
+        };
+
+        let response = handle_query(&query, zone, &mut cache, overlay);
+        socket.send_to(&response.to_bytes(), src)?;
+    }
+}
+
+fn handle_query(query: &DNSPacket, zone: &Zone, cache: &mut dyn CacheStore, overlay: &dyn ZoneStore) -> DNSPacket {
+    let question = match query.questions.first() {
+        Some(question) => question,
+        None => return build_response(query, Vec::new(), RCODE_FORMAT_ERROR)
+    };
+
+    let record_type = RecordType::from(question.type_);
+
+    if let Some(records) = zone.get(&(question.name.clone(), record_type)) {
+        return build_response(query, records.clone(), RCODE_NO_ERROR);
+    }
+
+    match resolve(&question.name, record_type, cache, overlay) {
+        Ok(answers) if !answers.is_empty() => build_response(query, answers, RCODE_NO_ERROR),
+        Ok(_) => build_response(query, Vec::new(), RCODE_NAME_ERROR),
+        Err(_) => build_response(query, Vec::new(), RCODE_SERVER_FAILURE)
+    }
+}
+
+fn build_response(query: &DNSPacket, answers: Vec<DNSRecord>, rcode: u16) -> DNSPacket {
+    let questions: Vec<DNSQuestion> = query.questions.clone();
+
+    let header = DNSHeader {
+        id: query.header.id,
+        flags: FLAG_QR_RESPONSE | rcode,
+        num_questions: questions.len() as u16,
+        num_answers: answers.len() as u16,
+        num_authorities: 0,
+        num_additionals: 0
+    };
+
+    DNSPacket {
+        header,
+        questions,
+        answers,
+        authorities: Vec::new(),
+        additionals: Vec::new()
+    }
+}
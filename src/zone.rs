@@ -0,0 +1,201 @@
+use crate::dns::{DNSRecord, RData, RecordType, CLASS_IN};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+
+/// A local, cryptographically-signed record for a custom top-level domain.
+/// The TLD of `name` is expected to equal `key_label(&public_key)`, so
+/// anyone holding a zone file can check that a record actually belongs to
+/// the key it claims, without trusting whoever handed them the file.
+pub struct DomainRecord {
+    pub name: String,
+    pub type_: RecordType,
+    pub data: RData,
+    pub ttl: u32,
+    pub signature: [u8; 64],
+    pub public_key: [u8; 32]
+}
+
+impl DomainRecord {
+    /// Checks both that `name`'s TLD is owned by `public_key` and that
+    /// `signature` is a valid ed25519 signature over `(name, type_, data)`.
+    pub fn verify(&self) -> bool {
+        if !owning_key_matches(&self.name, &self.public_key) {
+            return false;
+        }
+
+        let verifying_key = match VerifyingKey::from_bytes(&self.public_key) {
+            Ok(key) => key,
+            Err(_) => return false
+        };
+        let signature = Signature::from_bytes(&self.signature);
+        let message = signing_message(&self.name, self.type_, &self.data);
+
+        verifying_key.verify(&message, &signature).is_ok()
+    }
+}
+
+fn signing_message(name: &str, type_: RecordType, data: &RData) -> Vec<u8> {
+    let mut message = Vec::new();
+    message.extend_from_slice(name.as_bytes());
+    message.extend_from_slice(&u16::from(type_).to_be_bytes());
+    message.extend_from_slice(&data.to_bytes());
+    message
+}
+
+// The zone's TLD is the lowercase hex of the first 8 bytes of
+// sha256(public_key) -- a self-certifying name, so owning the key is
+// owning the zone, with no registry to ask.
+fn owning_key_matches(name: &str, public_key: &[u8; 32]) -> bool {
+    match name.rsplit('.').next() {
+        Some(tld) if !tld.is_empty() => tld.eq_ignore_ascii_case(&key_label(public_key)),
+        _ => false
+    }
+}
+
+fn key_label(public_key: &[u8; 32]) -> String {
+    let digest = Sha256::digest(public_key);
+    digest[..8].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Consulted before any upstream query, so a resolver can answer custom
+/// TLDs from local, signed records instead of the public DNS hierarchy.
+pub trait ZoneStore {
+    fn lookup(&self, name: &str, type_: RecordType) -> Option<Vec<DNSRecord>>;
+}
+
+/// A `ZoneStore` that never matches, for resolvers not running any managed
+/// zones.
+pub struct NoopZoneStore;
+
+impl ZoneStore for NoopZoneStore {
+    fn lookup(&self, _name: &str, _type_: RecordType) -> Option<Vec<DNSRecord>> {
+        None
+    }
+}
+
+/// In-memory `ZoneStore` over a set of verified `DomainRecord`s.
+#[derive(Default)]
+pub struct SignedZoneStore {
+    records: HashMap<(String, RecordType), Vec<DomainRecord>>
+}
+
+impl SignedZoneStore {
+    pub fn new() -> SignedZoneStore {
+        SignedZoneStore { records: HashMap::new() }
+    }
+
+    /// Verifies `record` before admitting it; returns `false` and leaves the
+    /// store untouched if its signature or owning-key hash doesn't check out.
+    pub fn add_record(&mut self, record: DomainRecord) -> bool {
+        if !record.verify() {
+            return false;
+        }
+        self.records.entry((record.name.clone(), record.type_)).or_default().push(record);
+        true
+    }
+}
+
+impl ZoneStore for SignedZoneStore {
+    fn lookup(&self, name: &str, type_: RecordType) -> Option<Vec<DNSRecord>> {
+        let records = self.records.get(&(name.to_string(), type_))?;
+        Some(records.iter().map(|record| DNSRecord {
+            name: record.name.clone(),
+            type_: record.type_,
+            class: CLASS_IN,
+            ttl: record.ttl,
+            data: record.data.clone()
+        }).collect())
+    }
+}
+
+/// Loads a [`SignedZoneStore`] from a zone file: one A record per line, as
+/// `name ttl ip signature_hex public_key_hex`, with blank lines and lines
+/// starting with `#` ignored. A line that's malformed or whose signature
+/// doesn't verify is skipped rather than failing the whole load -- one bad
+/// or stale record shouldn't keep the rest of the zone from serving.
+pub fn load_zone_file(path: &str) -> io::Result<SignedZoneStore> {
+    let contents = fs::read_to_string(path)?;
+    let mut store = SignedZoneStore::new();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(record) = parse_zone_line(line) {
+            store.add_record(record);
+        }
+    }
+
+    Ok(store)
+}
+
+fn parse_zone_line(line: &str) -> Option<DomainRecord> {
+    let mut fields = line.split_whitespace();
+    let name = fields.next()?.to_string();
+    let ttl = fields.next()?.parse().ok()?;
+    let ip: Ipv4Addr = fields.next()?.parse().ok()?;
+    let signature: [u8; 64] = decode_hex(fields.next()?)?.try_into().ok()?;
+    let public_key: [u8; 32] = decode_hex(fields.next()?)?.try_into().ok()?;
+
+    Some(DomainRecord {
+        name,
+        type_: RecordType::A,
+        data: RData::A(ip),
+        ttl,
+        signature,
+        public_key
+    })
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.is_ascii() || !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn signed_record(name: &str, signing_key: &SigningKey) -> DomainRecord {
+        let data = RData::A(Ipv4Addr::new(127, 0, 0, 1));
+        let message = signing_message(name, RecordType::A, &data);
+
+        DomainRecord {
+            name: name.to_string(),
+            type_: RecordType::A,
+            data,
+            ttl: 300,
+            signature: signing_key.sign(&message).to_bytes(),
+            public_key: signing_key.verifying_key().to_bytes()
+        }
+    }
+
+    #[test]
+    fn rejects_a_bit_flipped_signature() {
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let tld = key_label(&signing_key.verifying_key().to_bytes());
+        let mut record = signed_record(&format!("example.{}", tld), &signing_key);
+        assert!(record.verify());
+
+        record.signature[0] ^= 1;
+        assert!(!record.verify());
+    }
+
+    #[test]
+    fn rejects_a_tld_that_does_not_match_the_owning_key() {
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let record = signed_record("example.com", &signing_key);
+        assert!(!record.verify());
+    }
+}